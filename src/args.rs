@@ -1,12 +1,18 @@
 use clap::{ArgAction, Parser};
 
+use crate::ingest::Format;
+use crate::write::Op;
+
 /// Slurp: chunk JSON items and INSERT them into a SurrealDB table.
 ///
-/// Reads a JSON array from --data payload.json, splits it into --batch sized
-/// chunks, and performs parallel INSERTs into SurrealDB using SurrealQL.
+/// Reads records from --data (a JSON array file, a JSONL/NDJSON file, or `-`
+/// for STDIN), splits them into --batch sized chunks, and sends them to
+/// SurrealDB concurrently as SurrealQL writes (INSERT by default; see --op).
 ///
-/// Connection is built from --host and --port with no auth.
-#[derive(Parser, Debug)]
+/// Connection is built from --host and --port. Credentials are optional: pass
+/// --user/--pass (HTTP Basic) or --token (Bearer JWT), or set SURREAL_USER /
+/// SURREAL_PASS in the environment.
+#[derive(Parser)]
 #[command(version, about)]
 pub struct Args {
     /// SurrealDB host (no scheme)
@@ -29,15 +35,48 @@ pub struct Args {
     #[arg(long = "table")]
     pub table: String,
 
-    /// Path to a JSON array file (e.g., [ {..}, {..}, ... ])
+    /// SurrealDB username (falls back to $SURREAL_USER)
+    #[arg(long = "user", env = "SURREAL_USER")]
+    pub user: Option<String>,
+
+    /// SurrealDB password (falls back to $SURREAL_PASS)
+    #[arg(long = "pass", env = "SURREAL_PASS")]
+    pub pass: Option<String>,
+
+    /// Pre-issued JWT, sent as a Bearer token instead of --user/--pass
+    #[arg(long = "token")]
+    pub token: Option<String>,
+
+    /// Path to an input file (e.g., [ {..}, {..}, ... ] or one value per line),
+    /// or `-` to read from STDIN
     #[arg(long = "data")]
     pub data_path: String,
 
+    /// Input format: auto-detect, a JSON array, or line-delimited JSONL
+    #[arg(long = "format", value_enum, default_value_t = Format::Auto)]
+    pub format: Format,
+
+    /// Default write operation for records without an `_op` override
+    #[arg(long = "op", value_enum, default_value_t = Op::Insert)]
+    pub op: Op,
+
+    /// Maximum retry attempts per batch on transport or retryable HTTP errors
+    #[arg(long = "max-retries", default_value_t = 5)]
+    pub max_retries: usize,
+
+    /// Append records from permanently-failed batches to this JSONL file
+    #[arg(long = "dead-letter")]
+    pub dead_letter: Option<String>,
+
+    /// Write a machine-readable run report (inserted/failed/errors) to this file
+    #[arg(long = "report")]
+    pub report: Option<String>,
+
     /// Batch size (number of items per INSERT, must be > 0)
     #[arg(long = "batch", default_value_t = 500)]
     pub batch: usize,
 
-    /// Number of parallel worker threads (must be > 0)
+    /// Maximum number of batches in flight at once (concurrency cap, must be > 0)
     #[arg(long = "thread", default_value_t = 4)]
     pub threads: usize,
 
@@ -50,6 +89,34 @@ pub struct Args {
     pub dry_run: bool,
 }
 
+/// Manual `Debug` that redacts the credential fields so a stray `{args:?}`
+/// can never leak `--pass`/`--token` into logs.
+impl std::fmt::Debug for Args {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let redact = |v: &Option<String>| v.as_ref().map(|_| "<redacted>");
+        f.debug_struct("Args")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("ns", &self.ns)
+            .field("db", &self.db)
+            .field("table", &self.table)
+            .field("user", &self.user)
+            .field("pass", &redact(&self.pass))
+            .field("token", &redact(&self.token))
+            .field("data_path", &self.data_path)
+            .field("format", &self.format)
+            .field("op", &self.op)
+            .field("max_retries", &self.max_retries)
+            .field("dead_letter", &self.dead_letter)
+            .field("report", &self.report)
+            .field("batch", &self.batch)
+            .field("threads", &self.threads)
+            .field("verbosity", &self.verbosity)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
 impl Args {
     /// Build the SurrealDB /sql endpoint URL from host and port.
     pub fn sql_endpoint(&self) -> String {
@@ -0,0 +1,271 @@
+//! Streaming ingestion of JSON input into fixed-size batches.
+//!
+//! The array path (`[ {..}, {..}, ... ]`) is parsed up front, matching Slurp's
+//! original behaviour. The line-delimited path (NDJSON/JSONL, or `--data -`)
+//! is read through a `BufReader` one line at a time and flushed into a batch as
+//! soon as the buffer reaches `--batch`, so multi-gigabyte dumps ingest with
+//! bounded memory and inserts can start before the whole input is read.
+//!
+//! A JSONL line that fails to parse does not abort the run: it is carried
+//! alongside the batch it would have belonged to (see [`RecordBatch`]) so the
+//! caller can dead-letter the raw line and count it, instead of the record
+//! silently vanishing.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde_json::{self as json, Value};
+use tracing::warn;
+
+/// A JSONL line that failed to parse as JSON, kept verbatim so it can be
+/// dead-lettered rather than silently dropped.
+pub struct MalformedLine {
+    /// 1-based line number within the input, for diagnostics.
+    pub lineno: usize,
+    /// The raw (trimmed) line text.
+    pub raw: String,
+}
+
+/// One batch of up to `--batch` parsed records, plus any line that failed to
+/// parse while the batch was being filled.
+pub struct RecordBatch {
+    pub records: Vec<Value>,
+    pub malformed: Vec<MalformedLine>,
+}
+
+/// How a `--data` source is decoded into records.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Sniff the leading non-whitespace byte: `[` is a JSON array, anything
+    /// else is treated as JSONL.
+    Auto,
+    /// A single JSON array: `[ {..}, {..}, ... ]`.
+    Json,
+    /// One JSON value per line (NDJSON / JSONL).
+    Jsonl,
+}
+
+/// An iterator over record batches, each at most `--batch` items.
+pub struct Batches {
+    inner: Inner,
+}
+
+enum Inner {
+    /// Array input is split into chunks up front.
+    Array(std::vec::IntoIter<Vec<Value>>),
+    /// JSONL input is buffered and flushed batch-by-batch.
+    Lines(LineBatcher),
+}
+
+impl Iterator for Batches {
+    type Item = anyhow::Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            Inner::Array(it) => it.next().map(|records| {
+                Ok(RecordBatch {
+                    records,
+                    malformed: Vec::new(),
+                })
+            }),
+            Inner::Lines(b) => b.next_batch(),
+        }
+    }
+}
+
+/// Open `path` (or STDIN when `path` is `-`) and produce batches of `batch`
+/// records each, decoding according to `format`.
+pub fn batches(path: &str, format: Format, batch: usize) -> anyhow::Result<Batches> {
+    let mut reader: Box<dyn BufRead + Send> = if path == "-" {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        let file = File::open(path).with_context(|| format!("opening --data {path}"))?;
+        Box::new(BufReader::new(file))
+    };
+
+    let format = match format {
+        Format::Auto => sniff(&mut reader)?,
+        chosen => chosen,
+    };
+
+    let inner = match format {
+        Format::Json => {
+            let mut raw = String::new();
+            reader.read_to_string(&mut raw)?;
+            let value: Value = json::from_str(&raw)?;
+            let items = value
+                .as_array()
+                .ok_or_else(|| anyhow::anyhow!("input must be a JSON array"))?;
+            let chunks: Vec<Vec<Value>> = items.chunks(batch).map(|c| c.to_vec()).collect();
+            Inner::Array(chunks.into_iter())
+        }
+        // `Auto` has already been resolved above, so it cannot reach here.
+        Format::Jsonl | Format::Auto => Inner::Lines(LineBatcher {
+            reader,
+            batch,
+            lineno: 0,
+            skipped: 0,
+            done: false,
+        }),
+    };
+
+    Ok(Batches { inner })
+}
+
+/// Peek the first non-whitespace byte without consuming it to pick a format.
+fn sniff(reader: &mut dyn BufRead) -> anyhow::Result<Format> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            // Empty input: nothing to insert, treat it as (empty) JSONL.
+            return Ok(Format::Jsonl);
+        }
+        let mut ws = 0;
+        for &b in buf {
+            if b.is_ascii_whitespace() {
+                ws += 1;
+            } else {
+                reader.consume(ws);
+                return Ok(if b == b'[' { Format::Json } else { Format::Jsonl });
+            }
+        }
+        // The buffer was all whitespace; drop it and keep looking.
+        reader.consume(ws);
+    }
+}
+
+/// Buffers JSONL lines and flushes a batch once it reaches `batch` records.
+struct LineBatcher {
+    reader: Box<dyn BufRead + Send>,
+    batch: usize,
+    lineno: usize,
+    /// Count of malformed lines skipped so far, reported at end of input.
+    skipped: usize,
+    done: bool,
+}
+
+impl LineBatcher {
+    fn next_batch(&mut self) -> Option<anyhow::Result<RecordBatch>> {
+        if self.done {
+            return None;
+        }
+        let mut buf = Vec::with_capacity(self.batch);
+        let mut malformed = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => {
+                    self.done = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+            self.lineno += 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            match json::from_str::<Value>(trimmed) {
+                Ok(v) => {
+                    buf.push(v);
+                    if buf.len() >= self.batch {
+                        return Some(Ok(RecordBatch { records: buf, malformed }));
+                    }
+                }
+                // Keep the bad line instead of dropping it so one malformed
+                // record does not truncate a multi-gigabyte dump: it rides
+                // along with this batch so the caller can dead-letter it and
+                // count it, while the valid records already in `buf` are
+                // still flushed at the next batch boundary or EOF.
+                Err(e) => {
+                    self.skipped += 1;
+                    warn!("malformed JSONL line {}: {e}", self.lineno);
+                    malformed.push(MalformedLine {
+                        lineno: self.lineno,
+                        raw: trimmed.to_string(),
+                    });
+                }
+            }
+        }
+        if self.skipped > 0 {
+            warn!("ingest finished with {} malformed line(s) skipped", self.skipped);
+        }
+        if buf.is_empty() && malformed.is_empty() {
+            None
+        } else {
+            Some(Ok(RecordBatch { records: buf, malformed }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn line_batcher(input: &str, batch: usize) -> LineBatcher {
+        LineBatcher {
+            reader: Box::new(Cursor::new(input.as_bytes().to_vec())),
+            batch,
+            lineno: 0,
+            skipped: 0,
+            done: false,
+        }
+    }
+
+    #[test]
+    fn sniff_detects_json_array() {
+        let mut reader = Cursor::new(b"  [1, 2, 3]".to_vec());
+        assert_eq!(sniff(&mut reader).unwrap(), Format::Json);
+    }
+
+    #[test]
+    fn sniff_detects_jsonl() {
+        let mut reader = Cursor::new(b"\n{\"a\":1}\n{\"a\":2}\n".to_vec());
+        assert_eq!(sniff(&mut reader).unwrap(), Format::Jsonl);
+    }
+
+    #[test]
+    fn sniff_treats_empty_input_as_jsonl() {
+        let mut reader = Cursor::new(Vec::new());
+        assert_eq!(sniff(&mut reader).unwrap(), Format::Jsonl);
+    }
+
+    #[test]
+    fn line_batcher_splits_exactly_at_batch_size() {
+        let mut b = line_batcher("{\"a\":1}\n{\"a\":2}\n{\"a\":3}\n", 2);
+        let first = b.next_batch().unwrap().unwrap();
+        assert_eq!(first.records.len(), 2);
+        let second = b.next_batch().unwrap().unwrap();
+        assert_eq!(second.records.len(), 1);
+        assert!(b.next_batch().is_none());
+    }
+
+    #[test]
+    fn line_batcher_carries_malformed_line_without_truncating() {
+        let mut b = line_batcher("{\"a\":1}\nnot json\n{\"a\":2}\n", 10);
+        let batch = b.next_batch().unwrap().unwrap();
+        assert_eq!(batch.records.len(), 2);
+        assert_eq!(batch.malformed.len(), 1);
+        assert_eq!(batch.malformed[0].lineno, 2);
+        assert_eq!(batch.malformed[0].raw, "not json");
+        assert_eq!(b.skipped, 1);
+        assert!(b.next_batch().is_none());
+    }
+
+    #[test]
+    fn line_batcher_flushes_a_batch_of_only_malformed_lines() {
+        let mut b = line_batcher("not json\nalso not json\n", 10);
+        let batch = b.next_batch().unwrap().unwrap();
+        assert!(batch.records.is_empty());
+        assert_eq!(batch.malformed.len(), 2);
+        assert!(b.next_batch().is_none());
+    }
+}
@@ -1,182 +1,650 @@
-use std::fs;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use clap::{ArgAction, Parser};
-use rayon::prelude::*;
-use reqwest::blocking::Client;
-use reqwest::header::{ACCEPT, CONTENT_TYPE};
-use serde_json::{self as json, Value};
+use anyhow::Context;
+use futures::stream::{self, StreamExt};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
+mod args; // uses src/args.rs
+mod ingest; // uses src/ingest.rs
 mod log; // uses src/log.rs
+mod report; // uses src/report.rs
+mod write; // uses src/write.rs
 
-/// Slurp: chunk JSON items and INSERT them into a SurrealDB table.
-///
-/// Reads a JSON array from --data payload.json, splits it into --batch sized
-/// chunks, and performs parallel INSERTs into SurrealDB using SurrealQL.
-/// Connection URL is taken from env SURREAL_URL or defaults to http://localhost:8000.
-#[derive(Parser, Debug)]
-#[command(version, about)]
-struct Args {
-    /// SurrealDB namespace
-    #[arg(long = "ns")]
-    ns: String,
+use report::{OpCounts, Report, WriteError};
 
-    /// SurrealDB database
-    #[arg(long = "db")]
-    db: String,
+/// Base delay for exponential backoff (`base * 2^attempt`).
+const RETRY_BASE: Duration = Duration::from_millis(200);
+/// Ceiling applied to the exponential delay before jitter, and to `Retry-After`.
+const RETRY_CAP: Duration = Duration::from_secs(30);
 
-    /// Destination table name
-    #[arg(long = "table")]
-    table: String,
-
-    /// Path to a JSON array file (e.g., [ {..}, {..}, ... ])
-    #[arg(long = "data")]
-    data_path: String,
-
-    /// Batch size (number of items per INSERT)
-    #[arg(long = "batch", default_value_t = 500, value_parser = clap::value_parser!(usize).range(1..))]
-    batch: usize,
-
-    /// Number of parallel worker threads
-    #[arg(long = "thread", default_value_t = 4, value_parser = clap::value_parser!(usize).range(1..))]
-    threads: usize,
-
-    /// Verbosity level: 0=warn, 1=info, 2=debug
-    #[arg(long = "verbosity", default_value_t = 1, value_parser = clap::value_parser!(u8).range(0..=2))]
-    verbosity: u8,
-
-    /// Dry-run: parse and show what would be inserted, but do not send requests
-    #[arg(long = "dry-run", action = ArgAction::SetTrue)]
-    dry_run: bool,
-}
-
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = args::parse();
 
     // Init colorized, timestamped logging
     log::init(log::level_from_verbosity(args.verbosity));
 
-    // Resolve Surreal endpoint URL
-    let url = std::env::var("SURREAL_URL").unwrap_or_else(|_| "http://localhost:8000".to_string());
-    let sql_endpoint = format!("{}/sql", url);
+    // Resolve Surreal endpoint URL. SURREAL_URL still wins for ad-hoc runs;
+    // otherwise it is built from --host and --port.
+    let sql_endpoint = std::env::var("SURREAL_URL")
+        .map(|u| format!("{u}/sql"))
+        .unwrap_or_else(|_| args.sql_endpoint());
 
-    info!("loading JSON: {}", args.data_path);
-    let raw = fs::read_to_string(&args.data_path)?;
-    let value: Value = json::from_str(&raw)?;
+    info!("loading input: {}", args.data_path);
+    let mut batches = ingest::batches(&args.data_path, args.format, args.batch)?.peekable();
 
-    // Expect a JSON array of objects
-    let items = value
-        .as_array()
-        .ok_or_else(|| anyhow::anyhow!("input must be a JSON array"))?
-        .iter()
-        .cloned()
-        .collect::<Vec<Value>>();
-
-    if items.is_empty() {
+    // Short-circuit empty input before probing the server, so an empty dataset
+    // doesn't hard-fail against an unreachable SurrealDB for no reason.
+    if batches.peek().is_none() {
         warn!("no items found in input; nothing to insert");
         return Ok(());
     }
 
-    // Prepare the batches as immutable chunks
-    let batches: Vec<Vec<Value>> = items.chunks(args.batch).map(|c| c.to_vec()).collect();
-
-    info!(
-        "items: {}, batch size: {}, batches: {}, threads: {}",
-        items.len(),
-        args.batch,
-        batches.len(),
-        args.threads
-    );
+    info!("batch size: {}, concurrency: {}", args.batch, args.threads);
 
     if args.dry_run {
         info!("dry-run enabled; not sending INSERTs");
     }
 
-    // Shared HTTP client
+    // Sink for records whose batch permanently fails, so a run stays lossless.
+    let dead_letter = DeadLetter::open(args.dead_letter.as_deref())?;
+
+    // One async client shared across all batches: HTTP keep-alive plus a
+    // connection pool mean the `/sql` endpoint is reached over reused TCP
+    // connections instead of a fresh one per batch.
     let client = Client::builder()
         .timeout(Duration::from_secs(120))
         .build()?;
 
-    // Build a rayon pool with the requested thread count and run the work inside it
-    let (ok_count, err_count): (usize, usize) = rayon::ThreadPoolBuilder::new()
-        .num_threads(args.threads)
-        .build()?
-        .install(|| {
-            // Process in parallel; each batch maps to a Result
-            batches
-                .par_iter()
-                .enumerate()
-                .map(|(idx, batch)| {
-                    let stmt = build_insert_stmt(&args.table, batch)?;
-                    debug!("batch #{idx}: stmt size={}", stmt.len());
-
-                    if args.dry_run {
-                        info!("DRY batch #{idx}: {} records", batch.len());
-                        return Ok(());
-                    }
-
-                    // POST /sql with required headers
-                    let resp = client
-                        .post(&sql_endpoint)
-                        .header(ACCEPT, "application/json")
-                        .header(CONTENT_TYPE, "text/plain; charset=utf-8")
-                        .header("Surreal-NS", &args.ns)
-                        .header("Surreal-DB", &args.db)
-                        .body(stmt)
-                        .send();
-
-                    match resp {
-                        Ok(r) if r.status().is_success() => {
-                            if args.verbosity >= 2 {
-                                // In debug, try to read body to surface Surreal response messages
-                                let _ = r.text().map(|t| debug!("batch #{idx} ok: {t}"));
-                            }
-                            info!("batch #{idx} ok ({} records)", batch.len());
-                            Ok(())
-                        }
-                        Ok(r) => {
-                            let status = r.status();
-                            let text = r.text().unwrap_or_default();
-                            Err(anyhow::anyhow!(
-                                "batch #{idx} failed: HTTP {}: {}",
-                                status,
-                                text
-                            ))
-                        }
-                        Err(e) => Err(anyhow::anyhow!("batch #{idx} transport error: {e}")),
-                    }
-                })
-                // Fold success/failure counts immutably
-                .fold(
-                    || (0usize, 0usize),
-                    |(ok, err), res| {
-                        if res.is_ok() {
-                            (ok + 1, err)
-                        } else {
-                            (ok, err + 1)
-                        }
-                    },
-                )
-                .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1))
-        });
-
-    if err_count > 0 {
-        error!("done with errors: ok={}, err={}", ok_count, err_count);
+    // Validate connectivity (and any credentials) once up front so a
+    // misconfigured login fails fast instead of after every batch errors.
+    if !args.dry_run {
+        probe(&client, &sql_endpoint, &args).await?;
+        info!("connected to SurrealDB at {sql_endpoint}");
+    }
+
+    // Drive the batch stream with `buffer_unordered`, which caps the number of
+    // in-flight requests at `--thread` (now a concurrency limit, not an OS
+    // thread count) while the iterator feeds batches lazily to keep memory flat.
+    let mut summary = stream::iter(batches.enumerate())
+        .map(|(idx, batch_res)| {
+            process_batch(idx, batch_res, &args, &client, &sql_endpoint, &dead_letter)
+        })
+        .buffer_unordered(args.threads)
+        .fold(Summary::default(), |mut s, report| async move {
+            s.absorb(report);
+            s
+        })
+        .await;
+
+    dead_letter.flush();
+
+    info!(
+        "summary: batches ok={}, retried={}, failed={}; \
+         records inserted={}, upserted={}, updated={}, deleted={}, dead-lettered={}, malformed={}",
+        summary.ok,
+        summary.retried,
+        summary.failed,
+        summary.counts.inserted,
+        summary.counts.upserted,
+        summary.counts.updated,
+        summary.counts.deleted,
+        summary.dead_lettered,
+        summary.malformed
+    );
+
+    if let Some(path) = &args.report {
+        summary.write_report(path)?;
+        info!("wrote report: {path}");
+    }
+
+    if summary.failed > 0 || summary.malformed > 0 {
+        error!(
+            "done with errors: {} batch(es) failed, {} malformed line(s), {} record(s) dead-lettered",
+            summary.failed, summary.malformed, summary.dead_lettered
+        );
         // Exit non-zero so this can be scripted
         std::process::exit(1);
     } else {
-        info!("done: all {} batches ok", ok_count);
+        info!("done: all {} batches ok", summary.ok);
     }
 
     Ok(())
 }
 
-/// Build a single SurrealQL INSERT statement that inserts an array of objects.
-/// We ask SurrealDB not to echo large results back to us.
-fn build_insert_stmt(table: &str, batch: &[Value]) -> anyhow::Result<String> {
-    // Serialize the batch to a compact JSON array string
-    let json_array = serde_json::to_string(batch)?;
-    // INSERT INTO table [ {..}, {..}, ... ] RETURN NONE;
-    Ok(format!("INSERT INTO {table} {json_array} RETURN NONE;"))
+/// Running totals folded from every batch's [`BatchReport`].
+#[derive(Default)]
+struct Summary {
+    ok: usize,
+    retried: usize,
+    failed: usize,
+    batches: usize,
+    counts: OpCounts,
+    failed_records: usize,
+    dead_lettered: usize,
+    /// JSONL lines that failed to parse (dead-lettered verbatim when
+    /// `--dead-letter` is set).
+    malformed: usize,
+    errors: Vec<WriteError>,
+}
+
+impl Summary {
+    fn absorb(&mut self, mut report: BatchReport) {
+        self.batches += 1;
+        self.counts.absorb(&report.counts);
+        self.failed_records += report.failed_records;
+        self.dead_lettered += report.dead_lettered;
+        self.malformed += report.malformed;
+        self.errors.append(&mut report.errors);
+        if report.failed {
+            self.failed += 1;
+        } else {
+            self.ok += 1;
+            if report.retried {
+                self.retried += 1;
+            }
+        }
+    }
+
+    /// Serialize the machine-readable report to `path`.
+    fn write_report(&mut self, path: &str) -> anyhow::Result<()> {
+        let report = Report {
+            counts: std::mem::take(&mut self.counts),
+            failed: self.failed_records,
+            batches: self.batches,
+            malformed: self.malformed,
+            errors: std::mem::take(&mut self.errors),
+        };
+        let json = serde_json::to_string_pretty(&report)?;
+        std::fs::write(path, json).with_context(|| format!("writing --report {path}"))?;
+        Ok(())
+    }
+}
+
+/// The outcome of a single batch, used to build the run [`Summary`] and report.
+#[derive(Default)]
+struct BatchReport {
+    counts: OpCounts,
+    failed_records: usize,
+    dead_lettered: usize,
+    /// Malformed JSONL lines that rode along with this batch (see
+    /// [`ingest::RecordBatch`]); dead-lettered verbatim when `--dead-letter`
+    /// is set.
+    malformed: usize,
+    retried: bool,
+    failed: bool,
+    errors: Vec<WriteError>,
+}
+
+/// Build and POST one batch, retrying transient failures with backoff and
+/// dead-lettering the records when retries are exhausted.
+async fn process_batch(
+    idx: usize,
+    batch_res: anyhow::Result<ingest::RecordBatch>,
+    args: &args::Args,
+    client: &Client,
+    sql_endpoint: &str,
+    dead_letter: &DeadLetter,
+) -> BatchReport {
+    let ingest::RecordBatch {
+        records: batch,
+        malformed,
+    } = match batch_res {
+        Ok(b) => b,
+        // A parse/ingestion error has no usable records to dead-letter.
+        Err(e) => {
+            warn!("batch #{idx}: {e}");
+            return BatchReport {
+                failed: true,
+                errors: vec![fail(idx, e.to_string())],
+                ..Default::default()
+            };
+        }
+    };
+
+    // Lines that failed to parse ride along with the batch they would have
+    // belonged to; dead-letter them verbatim so they stay recoverable and
+    // count toward the run's failure signal instead of silently vanishing.
+    let malformed_count = malformed.len();
+    if malformed_count > 0 {
+        warn!("batch #{idx}: dead-lettering {malformed_count} malformed JSONL line(s)");
+    }
+    let malformed_dead_lettered = dead_letter.append_raw(&malformed);
+
+    if batch.is_empty() {
+        return BatchReport {
+            malformed: malformed_count,
+            dead_lettered: malformed_dead_lettered,
+            failed: malformed_count > 0,
+            ..Default::default()
+        };
+    }
+    let records = batch.len();
+
+    let compiled = match write::build_batch(&args.table, &batch, args.op) {
+        Ok(b) => b,
+        Err(e) => {
+            warn!("batch #{idx}: {e}");
+            return BatchReport {
+                failed_records: records,
+                dead_lettered: dead_letter.append(&batch) + malformed_dead_lettered,
+                malformed: malformed_count,
+                failed: true,
+                errors: vec![fail(idx, e.to_string())],
+                ..Default::default()
+            };
+        }
+    };
+    let stmt = compiled.sql();
+    debug!("batch #{idx}: stmt size={}", stmt.len());
+
+    if args.dry_run {
+        info!("DRY batch #{idx}: {records} records");
+        return BatchReport {
+            malformed: malformed_count,
+            dead_lettered: malformed_dead_lettered,
+            failed: malformed_count > 0,
+            ..Default::default()
+        };
+    }
+
+    let mut attempt: u32 = 0;
+    loop {
+        let resp = sql_request(client, sql_endpoint, args)
+            .body(stmt.clone())
+            .send()
+            .await;
+
+        match classify(resp).await {
+            Attempt::Ok(body) => {
+                let outcome = report::parse_response(idx, &body, &compiled.statements);
+                if outcome.failed_records.is_empty() {
+                    info!("batch #{idx} ok");
+                    return BatchReport {
+                        counts: outcome.counts,
+                        dead_lettered: malformed_dead_lettered,
+                        malformed: malformed_count,
+                        failed: malformed_count > 0,
+                        retried: attempt > 0,
+                        errors: outcome.errors,
+                        ..Default::default()
+                    };
+                }
+                // Dead-letter only the records behind the statements that
+                // actually failed, so re-feeding the file doesn't re-apply the
+                // records this batch already wrote.
+                warn!(
+                    "batch #{idx} had {} write error(s); dead-lettering {} record(s)",
+                    outcome.errors.len(),
+                    outcome.failed_records.len()
+                );
+                return BatchReport {
+                    counts: outcome.counts,
+                    failed_records: outcome.failed_records.len(),
+                    dead_lettered: dead_letter.append(&outcome.failed_records) + malformed_dead_lettered,
+                    malformed: malformed_count,
+                    retried: attempt > 0,
+                    failed: true,
+                    errors: outcome.errors,
+                };
+            }
+            Attempt::Fatal(msg) => {
+                warn!("batch #{idx} failed (non-retryable): {msg}");
+                return BatchReport {
+                    failed_records: records,
+                    dead_lettered: dead_letter.append(&batch) + malformed_dead_lettered,
+                    malformed: malformed_count,
+                    failed: true,
+                    errors: vec![fail(idx, msg)],
+                    ..Default::default()
+                };
+            }
+            Attempt::Retry(retry_after, msg) => {
+                if attempt as usize >= args.max_retries {
+                    warn!("batch #{idx} failed after {} retries: {msg}", args.max_retries);
+                    return BatchReport {
+                        failed_records: records,
+                        dead_lettered: dead_letter.append(&batch) + malformed_dead_lettered,
+                        malformed: malformed_count,
+                        retried: true,
+                        failed: true,
+                        errors: vec![fail(idx, msg)],
+                        ..Default::default()
+                    };
+                }
+                let delay = backoff(attempt, retry_after);
+                warn!(
+                    "batch #{idx} retry {}/{} in {:?}: {msg}",
+                    attempt + 1,
+                    args.max_retries,
+                    delay
+                );
+                sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Build a `/sql` POST request with the shared headers and any credentials.
+fn sql_request(client: &Client, sql_endpoint: &str, args: &args::Args) -> RequestBuilder {
+    let rb = client
+        .post(sql_endpoint)
+        .header(ACCEPT, "application/json")
+        .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+        .header("Surreal-NS", &args.ns)
+        .header("Surreal-DB", &args.db);
+    apply_auth(rb, args)
+}
+
+/// Attach `Authorization` when credentials are supplied: a Bearer token takes
+/// precedence, otherwise HTTP Basic from --user/--pass.
+fn apply_auth(rb: RequestBuilder, args: &args::Args) -> RequestBuilder {
+    if let Some(token) = &args.token {
+        rb.bearer_auth(token)
+    } else if let Some(user) = &args.user {
+        rb.basic_auth(user, args.pass.as_ref())
+    } else {
+        rb
+    }
+}
+
+/// Probe the server with a cheap `INFO FOR DB;` to fail fast on a bad host or
+/// a misconfigured login before any batch work starts.
+async fn probe(client: &Client, sql_endpoint: &str, args: &args::Args) -> anyhow::Result<()> {
+    let resp = sql_request(client, sql_endpoint, args)
+        .body("INFO FOR DB;")
+        .send()
+        .await
+        .context("connecting to SurrealDB")?;
+    let status = resp.status();
+    if !status.is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        anyhow::bail!("SurrealDB connectivity check failed: HTTP {status}: {text}");
+    }
+    Ok(())
+}
+
+/// A batch-level [`WriteError`] for a transport/HTTP failure.
+fn fail(batch: usize, message: String) -> WriteError {
+    WriteError {
+        batch,
+        status: "ERR".to_string(),
+        message,
+    }
+}
+
+/// The classification of one POST attempt.
+enum Attempt {
+    /// A 2xx response, carrying the body for per-statement tallying.
+    Ok(String),
+    /// A permanent error (e.g. a 4xx other than 408/429); do not retry.
+    Fatal(String),
+    /// A transient error; retry after the optional server-requested delay.
+    Retry(Option<Duration>, String),
+}
+
+/// Turn a send result into an [`Attempt`], reading the body for diagnostics.
+async fn classify(resp: reqwest::Result<Response>) -> Attempt {
+    match resp {
+        Ok(r) if r.status().is_success() => Attempt::Ok(r.text().await.unwrap_or_default()),
+        Ok(r) => {
+            let status = r.status();
+            let retry_after = parse_retry_after(r.headers());
+            let text = r.text().await.unwrap_or_default();
+            let msg = format!("HTTP {status}: {text}");
+            if is_retryable(status) {
+                Attempt::Retry(retry_after, msg)
+            } else {
+                Attempt::Fatal(msg)
+            }
+        }
+        // Transport-level errors (connection reset, timeout, ...) are transient.
+        Err(e) => Attempt::Retry(None, format!("transport error: {e}")),
+    }
+}
+
+/// Retryable statuses: request timeout, rate limiting, and any 5xx.
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// Parse an integer-seconds `Retry-After` header, ignoring HTTP-date forms.
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Compute the backoff for `attempt`: honor `Retry-After` when present,
+/// otherwise `base * 2^attempt` capped at [`RETRY_CAP`] with ±20% jitter.
+fn backoff(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    if let Some(ra) = retry_after {
+        return ra.min(RETRY_CAP);
+    }
+    let factor = 1u32 << attempt.min(16);
+    let capped = RETRY_BASE.saturating_mul(factor).min(RETRY_CAP);
+    let jittered = capped.as_millis() as f64 * (0.8 + 0.4 * rand::random::<f64>());
+    Duration::from_millis(jittered as u64)
+}
+
+/// Append-only JSONL sink for records belonging to permanently-failed batches.
+#[derive(Clone)]
+struct DeadLetter {
+    sink: Option<Arc<Mutex<BufWriter<std::fs::File>>>>,
+}
+
+impl DeadLetter {
+    /// Open (creating/appending) the dead-letter file, or a no-op sink.
+    fn open(path: Option<&str>) -> anyhow::Result<Self> {
+        let sink = match path {
+            Some(p) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(p)
+                    .with_context(|| format!("opening --dead-letter {p}"))?;
+                Some(Arc::new(Mutex::new(BufWriter::new(file))))
+            }
+            None => None,
+        };
+        Ok(Self { sink })
+    }
+
+    /// Append each record as one JSON line; returns how many were written.
+    fn append(&self, records: &[Value]) -> usize {
+        let Some(sink) = &self.sink else {
+            return 0;
+        };
+        let mut w = sink.lock().expect("dead-letter mutex poisoned");
+        let mut written = 0;
+        for rec in records {
+            let line = serde_json::to_string(rec).unwrap_or_else(|_| "null".to_string());
+            match writeln!(w, "{line}") {
+                Ok(()) => written += 1,
+                Err(e) => warn!("failed to write dead-letter record: {e}"),
+            }
+        }
+        written
+    }
+
+    /// Append each malformed JSONL line verbatim, tagged with its source line
+    /// number, so a line that failed to parse stays recoverable instead of
+    /// being silently dropped. Returns how many were written.
+    fn append_raw(&self, lines: &[ingest::MalformedLine]) -> usize {
+        let Some(sink) = &self.sink else {
+            return 0;
+        };
+        let mut w = sink.lock().expect("dead-letter mutex poisoned");
+        let mut written = 0;
+        for line in lines {
+            let wrapped = serde_json::json!({"_source_line": line.lineno, "raw": line.raw});
+            match writeln!(w, "{wrapped}") {
+                Ok(()) => written += 1,
+                Err(e) => warn!("failed to write dead-letter line: {e}"),
+            }
+        }
+        written
+    }
+
+    /// Flush buffered records to disk at the end of the run.
+    fn flush(&self) {
+        if let Some(sink) = &self.sink {
+            if let Err(e) = sink.lock().expect("dead-letter mutex poisoned").flush() {
+                warn!("failed to flush dead-letter file: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+
+    fn test_args(token: Option<&str>, user: Option<&str>, pass: Option<&str>) -> args::Args {
+        args::Args {
+            host: "localhost".to_string(),
+            port: 8000,
+            ns: "ns".to_string(),
+            db: "db".to_string(),
+            table: "events".to_string(),
+            user: user.map(str::to_string),
+            pass: pass.map(str::to_string),
+            token: token.map(str::to_string),
+            data_path: "-".to_string(),
+            format: ingest::Format::Auto,
+            op: write::Op::Insert,
+            max_retries: 5,
+            dead_letter: None,
+            report: None,
+            batch: 500,
+            threads: 4,
+            verbosity: 1,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn backoff_honors_retry_after_over_the_exponential_calc() {
+        let delay = backoff(10, Some(Duration::from_secs(3)));
+        assert_eq!(delay, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn backoff_caps_retry_after_at_the_ceiling() {
+        let delay = backoff(0, Some(Duration::from_secs(3600)));
+        assert_eq!(delay, RETRY_CAP);
+    }
+
+    #[test]
+    fn backoff_caps_the_exponential_calc_at_the_ceiling() {
+        // The capped delay still gets +/-20% jitter applied on top, so allow
+        // that margin rather than asserting an exact ceiling.
+        let delay = backoff(32, None);
+        let jitter_margin = Duration::from_millis((RETRY_CAP.as_millis() as f64 * 1.2) as u64);
+        assert!(delay <= jitter_margin);
+    }
+
+    #[test]
+    fn backoff_without_retry_after_grows_with_attempt() {
+        // Jitter is +/-20%, so compare order instead of exact values: attempt 0
+        // falls in 160..=240ms and attempt 3 in 1280..=1920ms.
+        let early = backoff(0, None);
+        let later = backoff(3, None);
+        assert!(later > early);
+    }
+
+    #[test]
+    fn is_retryable_covers_408_429_and_5xx() {
+        assert!(is_retryable(StatusCode::REQUEST_TIMEOUT));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+    }
+
+    #[test]
+    fn is_retryable_excludes_other_4xx_and_2xx() {
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable(StatusCode::OK));
+    }
+
+    #[test]
+    fn parse_retry_after_reads_integer_seconds() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn parse_retry_after_ignores_http_date_forms() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "Wed, 21 Oct 2026 07:28:00 GMT".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn parse_retry_after_missing_header_is_none() {
+        let headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn apply_auth_prefers_bearer_token_over_basic() {
+        let client = Client::new();
+        let args = test_args(Some("tok"), Some("user"), Some("pass"));
+        let req = apply_auth(client.get("http://localhost"), &args)
+            .build()
+            .unwrap();
+        let auth = req
+            .headers()
+            .get(reqwest::header::AUTHORIZATION)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(auth.starts_with("Bearer "));
+    }
+
+    #[test]
+    fn apply_auth_falls_back_to_basic_without_a_token() {
+        let client = Client::new();
+        let args = test_args(None, Some("user"), Some("pass"));
+        let req = apply_auth(client.get("http://localhost"), &args)
+            .build()
+            .unwrap();
+        let auth = req
+            .headers()
+            .get(reqwest::header::AUTHORIZATION)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(auth.starts_with("Basic "));
+    }
+
+    #[test]
+    fn apply_auth_sends_no_auth_header_without_credentials() {
+        let client = Client::new();
+        let args = test_args(None, None, None);
+        let req = apply_auth(client.get("http://localhost"), &args)
+            .build()
+            .unwrap();
+        assert!(!req.headers().contains_key(reqwest::header::AUTHORIZATION));
+    }
 }
@@ -0,0 +1,228 @@
+//! Structured per-run report of affected counts and collected write errors.
+//!
+//! SurrealDB answers a multi-statement `/sql` POST with a JSON array of result
+//! objects, each carrying a `status` (`OK`/`ERR`) and a `result`. The results
+//! line up 1:1 with the statements Slurp sent, so parsing them lets us attribute
+//! affected-record counts to the right operation, tally what was *actually*
+//! written, and pick out exactly the records behind a failed statement.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::write::{Op, Statement};
+
+/// A single write error (one statement's `ERR`, or a whole-batch transport/HTTP
+/// failure), tagged with its originating batch.
+#[derive(Debug, Serialize)]
+pub struct WriteError {
+    pub batch: usize,
+    pub status: String,
+    pub message: String,
+}
+
+/// Affected-record counts, broken down by operation so a `--op delete` run is
+/// never reported as records "inserted".
+#[derive(Debug, Default, Serialize)]
+pub struct OpCounts {
+    pub inserted: usize,
+    pub upserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl OpCounts {
+    /// Attribute `n` affected records to `op`.
+    fn add(&mut self, op: Op, n: usize) {
+        match op {
+            Op::Insert => self.inserted += n,
+            Op::Upsert => self.upserted += n,
+            Op::Update => self.updated += n,
+            Op::Delete => self.deleted += n,
+        }
+    }
+
+    /// Merge another set of counts into this one.
+    pub fn absorb(&mut self, other: &OpCounts) {
+        self.inserted += other.inserted;
+        self.upserted += other.upserted;
+        self.updated += other.updated;
+        self.deleted += other.deleted;
+    }
+}
+
+/// The machine-readable result emitted via `--report`.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    #[serde(flatten)]
+    pub counts: OpCounts,
+    pub failed: usize,
+    pub batches: usize,
+    /// JSONL lines that failed to parse (dead-lettered verbatim when
+    /// `--dead-letter` is set).
+    pub malformed: usize,
+    pub errors: Vec<WriteError>,
+}
+
+/// What a batch's `/sql` response revealed.
+#[derive(Default)]
+pub struct Outcome {
+    pub counts: OpCounts,
+    pub errors: Vec<WriteError>,
+    /// The original records behind any statement that returned `ERR` (or whose
+    /// result was missing), so only they are dead-lettered.
+    pub failed_records: Vec<Value>,
+}
+
+/// Parse a `/sql` response body for `batch`, attributing affected counts per
+/// statement and collecting the records behind any failed statement.
+pub fn parse_response(batch: usize, body: &str, statements: &[Statement]) -> Outcome {
+    let mut out = Outcome::default();
+
+    let value: Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(e) => {
+            out.errors.push(WriteError {
+                batch,
+                status: "ERR".to_string(),
+                message: format!("unparseable response body: {e}"),
+            });
+            fail_all(&mut out, statements);
+            return out;
+        }
+    };
+
+    let Some(results) = value.as_array() else {
+        out.errors.push(WriteError {
+            batch,
+            status: "ERR".to_string(),
+            message: "response was not a JSON array".to_string(),
+        });
+        fail_all(&mut out, statements);
+        return out;
+    };
+
+    for (idx, stmt) in statements.iter().enumerate() {
+        let Some(item) = results.get(idx) else {
+            // Fewer results than statements: conservatively treat as failed.
+            out.errors.push(WriteError {
+                batch,
+                status: "ERR".to_string(),
+                message: format!("missing result for statement {idx}"),
+            });
+            out.failed_records.extend(stmt.records.iter().cloned());
+            continue;
+        };
+
+        let status = item.get("status").and_then(Value::as_str).unwrap_or("");
+        if status == "OK" {
+            // `result` is the array of affected records for this statement.
+            let affected = match item.get("result") {
+                Some(Value::Array(rows)) => rows.len(),
+                _ => 0,
+            };
+            out.counts.add(stmt.op, affected);
+        } else {
+            let message = item
+                .get("result")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| "unknown write error".to_string());
+            out.errors.push(WriteError {
+                batch,
+                status: status.to_string(),
+                message,
+            });
+            out.failed_records.extend(stmt.records.iter().cloned());
+        }
+    }
+
+    out
+}
+
+/// Mark every statement's records as failed (unparseable/mis-shaped response).
+fn fail_all(out: &mut Outcome, statements: &[Statement]) {
+    for stmt in statements {
+        out.failed_records.extend(stmt.records.iter().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stmt(op: Op, sql: &str, records: Vec<Value>) -> Statement {
+        Statement {
+            op,
+            sql: sql.to_string(),
+            records,
+        }
+    }
+
+    #[test]
+    fn all_ok_tallies_affected_counts_per_op() {
+        let statements = vec![
+            stmt(Op::Insert, "INSERT ...", vec![json!({"a": 1}), json!({"a": 2})]),
+            stmt(Op::Delete, "DELETE ...", vec![json!({"id": "x:1"})]),
+        ];
+        let body = r#"[
+            {"status": "OK", "result": [{"a": 1}, {"a": 2}]},
+            {"status": "OK", "result": [{"id": "x:1"}]}
+        ]"#;
+        let out = parse_response(0, body, &statements);
+        assert_eq!(out.counts.inserted, 2);
+        assert_eq!(out.counts.deleted, 1);
+        assert!(out.errors.is_empty());
+        assert!(out.failed_records.is_empty());
+    }
+
+    #[test]
+    fn embedded_err_dead_letters_only_that_statement() {
+        let statements = vec![
+            stmt(Op::Insert, "INSERT ...", vec![json!({"a": 1})]),
+            stmt(Op::Update, "UPDATE ...", vec![json!({"id": "x:2"})]),
+        ];
+        let body = r#"[
+            {"status": "OK", "result": [{"a": 1}]},
+            {"status": "ERR", "result": "some db error"}
+        ]"#;
+        let out = parse_response(0, body, &statements);
+        assert_eq!(out.counts.inserted, 1);
+        assert_eq!(out.counts.updated, 0);
+        assert_eq!(out.errors.len(), 1);
+        assert_eq!(out.errors[0].message, "some db error");
+        assert_eq!(out.failed_records, vec![json!({"id": "x:2"})]);
+    }
+
+    #[test]
+    fn fewer_results_than_statements_fails_the_missing_ones() {
+        let statements = vec![
+            stmt(Op::Insert, "INSERT ...", vec![json!({"a": 1})]),
+            stmt(Op::Insert, "INSERT ...", vec![json!({"a": 2})]),
+        ];
+        let body = r#"[{"status": "OK", "result": [{"a": 1}]}]"#;
+        let out = parse_response(0, body, &statements);
+        assert_eq!(out.counts.inserted, 1);
+        assert_eq!(out.errors.len(), 1);
+        assert!(out.errors[0].message.contains("missing result"));
+        assert_eq!(out.failed_records, vec![json!({"a": 2})]);
+    }
+
+    #[test]
+    fn unparseable_body_fails_every_statement() {
+        let statements = vec![stmt(Op::Insert, "INSERT ...", vec![json!({"a": 1})])];
+        let out = parse_response(0, "not json", &statements);
+        assert_eq!(out.errors.len(), 1);
+        assert!(out.errors[0].message.contains("unparseable response body"));
+        assert_eq!(out.failed_records, vec![json!({"a": 1})]);
+    }
+
+    #[test]
+    fn non_array_body_fails_every_statement() {
+        let statements = vec![stmt(Op::Insert, "INSERT ...", vec![json!({"a": 1})])];
+        let out = parse_response(0, r#"{"status": "OK"}"#, &statements);
+        assert_eq!(out.errors.len(), 1);
+        assert_eq!(out.errors[0].message, "response was not a JSON array");
+        assert_eq!(out.failed_records, vec![json!({"a": 1})]);
+    }
+}
@@ -0,0 +1,258 @@
+//! Builds SurrealQL write statements for a batch of records.
+//!
+//! A batch may mix operations: the default `--op` applies to a record unless it
+//! carries a reserved `_op` key. Consecutive records sharing an operation are
+//! grouped so the batch is still sent to `/sql` as a single multi-statement
+//! body (inserts coalesce into one array statement; the others emit one
+//! statement per record, targeted by `id`; an `UPSERT` without an `id` lets
+//! SurrealDB mint one).
+//!
+//! Statements return their affected records so the caller can tally what was
+//! actually written from the response (see [`crate::report`]).
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde_json::Value;
+
+/// A write operation applied to a record (or a run of records).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Op {
+    /// `INSERT INTO table [...]` — create new records.
+    Insert,
+    /// `UPSERT` — create the record, or replace it when its id already exists.
+    Upsert,
+    /// `UPDATE ... MERGE ...` — merge fields into an existing record.
+    Update,
+    /// `DELETE` — remove a record by id.
+    Delete,
+}
+
+impl Op {
+    /// Parse the value of a record's `_op` control key.
+    fn from_tag(tag: &str) -> Option<Op> {
+        match tag {
+            "insert" => Some(Op::Insert),
+            "upsert" => Some(Op::Upsert),
+            "update" => Some(Op::Update),
+            "delete" => Some(Op::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// Reserved control key that overrides `--op` for a single record.
+const OP_KEY: &str = "_op";
+/// Record-identifier key used to target UPSERT / UPDATE / DELETE.
+const ID_KEY: &str = "id";
+
+/// One SurrealQL statement together with the op it performs and the original
+/// records behind it, so the caller can attribute the response result per
+/// statement and dead-letter only the records of a statement that failed.
+pub struct Statement {
+    pub op: Op,
+    pub sql: String,
+    pub records: Vec<Value>,
+}
+
+/// A batch compiled into its per-statement pieces. The statements keep the same
+/// order they are POSTed in, so SurrealDB's result array lines up 1:1.
+pub struct Batch {
+    pub statements: Vec<Statement>,
+}
+
+impl Batch {
+    /// The combined multi-statement body sent to `/sql`.
+    pub fn sql(&self) -> String {
+        let mut out = String::new();
+        for stmt in &self.statements {
+            out.push_str(&stmt.sql);
+        }
+        out
+    }
+}
+
+/// Compile `batch` into per-statement pieces, applying `default_op` to any
+/// record without an `_op` override. Consecutive same-op records are grouped so
+/// inserts coalesce into one array statement; the others emit one statement per
+/// record. Each statement retains the original (un-stripped) records so a
+/// dead-lettered batch can be re-fed verbatim.
+pub fn build_batch(table: &str, batch: &[Value], default_op: Op) -> anyhow::Result<Batch> {
+    // Resolve each record's op, keeping the original alongside a cleaned copy.
+    let mut prepared: Vec<(Op, &Value, Value)> = Vec::with_capacity(batch.len());
+    for rec in batch {
+        let obj = rec
+            .as_object()
+            .ok_or_else(|| anyhow!("each record must be a JSON object"))?;
+        let mut cleaned = obj.clone();
+        let op = match cleaned.remove(OP_KEY) {
+            Some(v) => {
+                let tag = v.as_str().ok_or_else(|| anyhow!("`_op` must be a string"))?;
+                Op::from_tag(tag).ok_or_else(|| anyhow!("unknown `_op` value: {tag}"))?
+            }
+            None => default_op,
+        };
+        prepared.push((op, rec, Value::Object(cleaned)));
+    }
+
+    // Emit one statement (or array statement) per run of same-op records.
+    let mut statements = Vec::new();
+    let mut i = 0;
+    while i < prepared.len() {
+        let op = prepared[i].0;
+        let mut j = i;
+        while j < prepared.len() && prepared[j].0 == op {
+            j += 1;
+        }
+        let run = &prepared[i..j];
+        let records: Vec<Value> = run.iter().map(|(_, orig, _)| (*orig).clone()).collect();
+        match op {
+            Op::Insert => {
+                let arr: Vec<&Value> = run.iter().map(|(_, _, cleaned)| cleaned).collect();
+                let json = serde_json::to_string(&arr)?;
+                statements.push(Statement {
+                    op,
+                    sql: format!("INSERT INTO {table} {json};"),
+                    records,
+                });
+            }
+            Op::Upsert | Op::Update | Op::Delete => {
+                for ((_, _, cleaned), rec) in run.iter().zip(records) {
+                    let sql = match op {
+                        Op::Upsert => upsert_stmt(table, cleaned)?,
+                        Op::Update => update_stmt(table, cleaned)?,
+                        _ => delete_stmt(table, cleaned)?,
+                    };
+                    statements.push(Statement {
+                        op,
+                        sql,
+                        records: vec![rec],
+                    });
+                }
+            }
+        }
+        i = j;
+    }
+    Ok(Batch { statements })
+}
+
+/// `UPSERT type::thing(table, id) CONTENT {..}` — or an id-less UPSERT that lets
+/// SurrealDB mint the id when the record carries none. As in [`update_stmt`],
+/// `id` targets the record and is dropped from the content body.
+fn upsert_stmt(table: &str, rec: &Value) -> anyhow::Result<String> {
+    let mut obj = rec
+        .as_object()
+        .ok_or_else(|| anyhow!("each record must be a JSON object"))?
+        .clone();
+    match obj.remove(ID_KEY) {
+        Some(id) => {
+            let content = serde_json::to_string(&Value::Object(obj))?;
+            Ok(format!("UPSERT {} CONTENT {content};", thing(table, &id)?))
+        }
+        None => {
+            let content = serde_json::to_string(&Value::Object(obj))?;
+            Ok(format!("UPSERT {table} CONTENT {content};"))
+        }
+    }
+}
+
+/// `UPDATE type::thing(table, id) MERGE {..}` — `id` targets the record and is
+/// dropped from the merged content.
+fn update_stmt(table: &str, rec: &Value) -> anyhow::Result<String> {
+    let mut obj = rec
+        .as_object()
+        .ok_or_else(|| anyhow!("each record must be a JSON object"))?
+        .clone();
+    let id = obj
+        .remove(ID_KEY)
+        .ok_or_else(|| anyhow!("update requires an `id`"))?;
+    let content = serde_json::to_string(&Value::Object(obj))?;
+    Ok(format!(
+        "UPDATE {} MERGE {content};",
+        thing(table, &id)?
+    ))
+}
+
+/// `DELETE type::thing(table, id) RETURN BEFORE` — `DELETE` returns nothing by
+/// default, so the removed record is asked for explicitly to be counted.
+fn delete_stmt(table: &str, rec: &Value) -> anyhow::Result<String> {
+    let id = rec
+        .get(ID_KEY)
+        .ok_or_else(|| anyhow!("delete requires an `id`"))?;
+    Ok(format!("DELETE {} RETURN BEFORE;", thing(table, id)?))
+}
+
+/// Render a `type::thing(table, id)` record reference, letting SurrealDB build
+/// the record id so arbitrary id types (string, int, ...) are handled safely.
+///
+/// A JSON snapshot typically stores `id` as a full record reference
+/// (`"table:abc123"`, as SurrealDB's own export does); such a value is split on
+/// its first `:` and targeted at its embedded table — but only when the part
+/// before the `:` is exactly `table`, so ids that merely contain a colon
+/// (timestamps, IPv6 addresses, URLs, ...) are kept whole and targeted at
+/// `table` like any other literal id, instead of being silently misrouted.
+fn thing(table: &str, id: &Value) -> anyhow::Result<String> {
+    if let Some((tb, rest)) = id.as_str().and_then(|s| s.split_once(':')) {
+        if tb == table {
+            return Ok(format!(
+                "type::thing({}, {})",
+                serde_json::to_string(tb)?,
+                serde_json::to_string(rest)?
+            ));
+        }
+    }
+    Ok(format!(
+        "type::thing({}, {})",
+        serde_json::to_string(table)?,
+        serde_json::to_string(id)?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn thing_splits_a_full_record_reference_matching_the_table() {
+        let sql = thing("events", &json!("events:abc123")).unwrap();
+        assert_eq!(sql, r#"type::thing("events", "abc123")"#);
+    }
+
+    #[test]
+    fn thing_keeps_a_colon_bearing_id_whole_when_table_does_not_match() {
+        // A timestamp id should not be misread as a `"2024-01-01T10":"30:00Z"`
+        // record reference into some other table.
+        let sql = thing("events", &json!("2024-01-01T10:30:00Z")).unwrap();
+        assert_eq!(sql, r#"type::thing("events", "2024-01-01T10:30:00Z")"#);
+    }
+
+    #[test]
+    fn thing_wraps_a_plain_id() {
+        let sql = thing("events", &json!("abc123")).unwrap();
+        assert_eq!(sql, r#"type::thing("events", "abc123")"#);
+    }
+
+    #[test]
+    fn update_stmt_drops_id_from_merged_content() {
+        let sql = update_stmt("events", &json!({"id": "events:1", "a": 1})).unwrap();
+        assert_eq!(
+            sql,
+            r#"UPDATE type::thing("events", "1") MERGE {"a":1};"#
+        );
+    }
+
+    #[test]
+    fn build_batch_groups_consecutive_same_op_records() {
+        let batch = vec![
+            json!({"_op": "insert", "a": 1}),
+            json!({"_op": "insert", "a": 2}),
+            json!({"_op": "delete", "id": "events:3"}),
+        ];
+        let compiled = build_batch("events", &batch, Op::Insert).unwrap();
+        assert_eq!(compiled.statements.len(), 2);
+        assert_eq!(compiled.statements[0].op, Op::Insert);
+        assert_eq!(compiled.statements[0].records.len(), 2);
+        assert_eq!(compiled.statements[1].op, Op::Delete);
+        assert_eq!(compiled.statements[1].records.len(), 1);
+    }
+}